@@ -0,0 +1,121 @@
+use scraper::node::Node;
+use scraper::{Html, Selector};
+
+const DROP_TAGS: &[&str] = &["script", "style", "nav", "iframe", "svg"];
+
+/// Downloads `url` and extracts a readable, markdown version of its main
+/// content, so a link post can be read offline instead of requiring the
+/// eink device to follow the link.
+pub async fn fetch_article_markdown(url: &str) -> reqwest::Result<String> {
+    let html = crate::http::CLIENT.get(url).send().await?.text().await?;
+    Ok(extract_markdown(&html))
+}
+
+fn extract_markdown(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let body_selector = Selector::parse("body").unwrap();
+
+    let mut markdown = String::new();
+    if let Some(body) = document.select(&body_selector).next() {
+        for child in body.children() {
+            walk(child, &mut markdown);
+        }
+    }
+    collapse_blank_lines(&markdown)
+}
+
+/// Walks the parsed tree, skipping `script`/`style`/`nav`/`iframe`/`svg`
+/// subtrees entirely, collecting text content and rendering headings back
+/// out as markdown `#` lines.
+fn walk(node: ego_tree::NodeRef<Node>, out: &mut String) {
+    match node.value() {
+        Node::Element(element) => {
+            let tag = element.name();
+            if DROP_TAGS.contains(&tag) {
+                return;
+            }
+            if let Some(level) = heading_level(tag) {
+                out.push_str("\n\n");
+                out.push_str(&"#".repeat(level));
+                out.push(' ');
+                for child in node.children() {
+                    walk(child, out);
+                }
+                out.push_str("\n\n");
+                return;
+            }
+            for child in node.children() {
+                walk(child, out);
+            }
+            if tag == "p" || tag == "li" || tag == "div" {
+                out.push_str("\n\n");
+            }
+        }
+        Node::Text(text) => out.push_str(text),
+        _ => (),
+    }
+}
+
+fn heading_level(tag: &str) -> Option<usize> {
+    match tag {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+fn collapse_blank_lines(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut blank_run = 0;
+    for line in markdown.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run <= 1 {
+                out.push('\n');
+            }
+        } else {
+            blank_run = 0;
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_script_and_style_subtrees() {
+        let html = "<html><body><script>evil()</script><style>.a{}</style><p>Hello</p></body></html>";
+        assert_eq!(extract_markdown(html), "Hello");
+    }
+
+    #[test]
+    fn drops_nav_and_iframe_content() {
+        let html = "<html><body><nav>Menu</nav><iframe src='x'></iframe><p>Article</p></body></html>";
+        assert_eq!(extract_markdown(html), "Article");
+    }
+
+    #[test]
+    fn renders_headings_with_hash_prefixes() {
+        let html = "<html><body><h2>Title</h2><p>Body text</p></body></html>";
+        assert_eq!(extract_markdown(html), "## Title\n\nBody text");
+    }
+
+    #[test]
+    fn collapse_blank_lines_limits_runs_to_one() {
+        assert_eq!(collapse_blank_lines("a\n\n\n\nb\n\nc"), "a\n\nb\n\nc");
+    }
+
+    #[test]
+    fn collapse_blank_lines_trims_surrounding_whitespace() {
+        assert_eq!(collapse_blank_lines("\n\n  a  \n\n"), "a");
+    }
+}
@@ -0,0 +1,105 @@
+use moka::future::Cache;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A TTL-bounded async cache keyed by string, used to avoid re-fetching the
+/// same listing page or comment tree within one invocation (for instance,
+/// across several `--config` jobs). Entries live in memory only and are not
+/// kept from one invocation to the next.
+///
+/// A ttl of zero disables caching outright: no lookup or moka cache is even
+/// consulted, so every fetch reaches the Reddit API.
+pub struct FetchCache<T> {
+    cache: Option<Cache<String, Arc<T>>>,
+}
+
+impl<T> FetchCache<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    pub fn new(ttl_secs: u64) -> Self {
+        let cache = if ttl_secs == 0 {
+            None
+        } else {
+            Some(
+                Cache::builder()
+                    .time_to_live(Duration::from_secs(ttl_secs))
+                    .build(),
+            )
+        };
+        FetchCache { cache }
+    }
+
+    pub async fn get_or_fetch<F, Fut, E>(&self, key: &str, fetch: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return fetch().await,
+        };
+
+        if let Some(value) = cache.get(key).await {
+            return Ok((*value).clone());
+        }
+        let value = fetch().await?;
+        cache.insert(key.to_string(), Arc::new(value.clone())).await;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn zero_ttl_disables_caching() {
+        let cache = FetchCache::<u32>::new(0);
+        let calls = AtomicU32::new(0);
+        for _ in 0..2 {
+            cache
+                .get_or_fetch("key", || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok::<u32, Infallible>(42)
+                })
+                .await
+                .unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn nonzero_ttl_reuses_cached_value() {
+        let cache = FetchCache::<u32>::new(60);
+        let calls = AtomicU32::new(0);
+        for _ in 0..2 {
+            let value = cache
+                .get_or_fetch("key", || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok::<u32, Infallible>(42)
+                })
+                .await
+                .unwrap();
+            assert_eq!(value, 42);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn distinct_keys_are_cached_independently() {
+        let cache = FetchCache::<u32>::new(60);
+        let a = cache
+            .get_or_fetch("a", || async { Ok::<u32, Infallible>(1) })
+            .await
+            .unwrap();
+        let b = cache
+            .get_or_fetch("b", || async { Ok::<u32, Infallible>(2) })
+            .await
+            .unwrap();
+        assert_eq!((a, b), (1, 2));
+    }
+}
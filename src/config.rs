@@ -0,0 +1,101 @@
+use serde::Deserialize;
+
+/// A batch of jobs loaded from a `--config` TOML file.
+///
+/// Top-level `posts` and `converter_args` act as defaults for every job;
+/// a job may override either one. This lets a user describe a whole
+/// "reading list" (several subreddits, one output file each) in a single
+/// file instead of invoking the tool once per subreddit.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(default = "default_posts")]
+    pub posts: u32,
+    #[serde(default)]
+    pub converter_args: Option<String>,
+    pub jobs: Vec<Job>,
+}
+
+/// A single subreddit to fetch, with its own output path and optional
+/// overrides of the config-level defaults.
+#[derive(Debug, Deserialize)]
+pub struct Job {
+    pub subreddit: String,
+    pub output: String,
+    #[serde(default)]
+    pub posts: Option<u32>,
+    #[serde(default)]
+    pub converter_args: Option<String>,
+}
+
+fn default_posts() -> u32 {
+    10
+}
+
+impl Config {
+    pub fn load(path: &std::path::Path) -> std::io::Result<Config> {
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posts_defaults_to_ten_when_unset() {
+        let config: Config = toml::from_str(
+            r#"
+            [[jobs]]
+            subreddit = "books"
+            output = "books.md"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.posts, 10);
+    }
+
+    #[test]
+    fn job_inherits_unset_fields_from_config_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            posts = 5
+            converter_args = "--foo"
+
+            [[jobs]]
+            subreddit = "rust"
+            output = "rust.md"
+            "#,
+        )
+        .unwrap();
+        let job = &config.jobs[0];
+        assert_eq!(job.posts.unwrap_or(config.posts), 5);
+        assert_eq!(
+            job.converter_args.as_ref().or(config.converter_args.as_ref()),
+            Some(&String::from("--foo"))
+        );
+    }
+
+    #[test]
+    fn job_overrides_config_defaults_when_set() {
+        let config: Config = toml::from_str(
+            r#"
+            posts = 5
+            converter_args = "--foo"
+
+            [[jobs]]
+            subreddit = "rust"
+            output = "rust.md"
+            posts = 20
+            converter_args = "--bar"
+            "#,
+        )
+        .unwrap();
+        let job = &config.jobs[0];
+        assert_eq!(job.posts.unwrap_or(config.posts), 20);
+        assert_eq!(
+            job.converter_args.as_ref().or(config.converter_args.as_ref()),
+            Some(&String::from("--bar"))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use epub_builder::{EpubBuilder, EpubContent, ReferenceType, ZipLibrary};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+/// One post, rendered as markdown, that becomes one chapter of the epub.
+pub struct Chapter {
+    pub title: String,
+    pub markdown: String,
+}
+
+fn to_io_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// Escapes the characters that would otherwise break XML well-formedness
+/// when dropped raw into a tag body or attribute.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn markdown_to_xhtml(markdown: &str, title: &str) -> String {
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, pulldown_cmark::Parser::new(markdown));
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\">\n\
+         <head><title>{}</title></head>\n\
+         <body>\n{}\n</body>\n\
+         </html>",
+        escape_xml(title),
+        body
+    )
+}
+
+/// Returns the markdown image targets (`![](target)`) referenced in `markdown`.
+fn image_paths_in_markdown(markdown: &str) -> Vec<&str> {
+    let mut paths = Vec::new();
+    let mut rest = markdown;
+    while let Some(start) = rest.find("![](") {
+        rest = &rest[start + "![](".len()..];
+        if let Some(end) = rest.find(')') {
+            paths.push(&rest[..end]);
+            rest = &rest[end + 1..];
+        } else {
+            break;
+        }
+    }
+    paths
+}
+
+fn mime_type_for(path: &str) -> &'static str {
+    match Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+        .as_str()
+    {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+/// Builds a standalone EPUB with one chapter per post and a generated table
+/// of contents, with no dependency on an external ebook-convert binary.
+///
+/// Images referenced by chapters (via chunk0-3's `![](assets_dir/file)`
+/// markdown) are read back from disk, relative to `output`'s directory,
+/// and embedded as resources so they survive inside the EPUB container.
+pub fn write_epub(output: &Path, subreddit_name: &str, chapters: &[Chapter]) -> io::Result<()> {
+    let mut builder =
+        EpubBuilder::new(ZipLibrary::new().map_err(to_io_error)?).map_err(to_io_error)?;
+    builder
+        .metadata("title", format!("/r/{}", subreddit_name))
+        .map_err(to_io_error)?;
+    builder
+        .metadata("author", "reddit2eink")
+        .map_err(to_io_error)?;
+    builder.inline_toc();
+
+    let base_dir = output.parent().unwrap_or_else(|| Path::new("."));
+    let mut added_images = HashSet::new();
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        let xhtml = markdown_to_xhtml(&chapter.markdown, &chapter.title);
+        let file_name = format!("chapter_{}.xhtml", index);
+        builder
+            .add_content(
+                EpubContent::new(file_name, xhtml.as_bytes())
+                    .title(chapter.title.clone())
+                    .reftype(ReferenceType::Text),
+            )
+            .map_err(to_io_error)?;
+
+        for image_path in image_paths_in_markdown(&chapter.markdown) {
+            if !added_images.insert(image_path.to_string()) {
+                continue;
+            }
+            if let Ok(bytes) = std::fs::read(base_dir.join(image_path)) {
+                builder
+                    .add_resource(image_path, bytes.as_slice(), mime_type_for(image_path))
+                    .map_err(to_io_error)?;
+            }
+        }
+    }
+
+    let mut out_file = std::fs::File::create(output)?;
+    builder.generate(&mut out_file).map_err(to_io_error)?;
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use once_cell::sync::Lazy;
+use std::time::Duration;
+
+/// Shared HTTP client for downloading images and linked articles, with a
+/// conservative timeout so one slow or unresponsive host can't hang an
+/// entire unattended `--config` batch run.
+pub static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("cannot build http client")
+});
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+const IMAGE_EXTENSIONS: &[&str] = &[".jpg", ".jpeg", ".png", ".gif", ".webp"];
+
+/// Strips the query string and fragment off a URL, leaving just the path,
+/// so preview/CDN URLs like `.../abc.jpg?width=108&auto=webp` still resolve
+/// to the right extension.
+fn url_path(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+/// True when `url` looks like it points directly at an image file.
+fn is_image_url(url: &str) -> bool {
+    let path = url_path(url).to_lowercase();
+    IMAGE_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+}
+
+/// Downloads the image backing a submission into `assets_dir`, named after
+/// the post id, and returns a path to embed in markdown, relative to the
+/// directory the output file lives in.
+///
+/// Prefers `url` when it points directly at an image, falling back to
+/// `thumbnail` when Reddit gives us one. Returns `Ok(None)` when the post
+/// has no usable image, or when the image is larger than `max_size` bytes.
+pub async fn fetch_post_image(
+    post_id: &str,
+    url: &str,
+    thumbnail: &str,
+    assets_dir: &Path,
+    max_size: u64,
+) -> reqwest::Result<Option<PathBuf>> {
+    let image_url = if is_image_url(url) {
+        url
+    } else if thumbnail.starts_with("http") {
+        thumbnail
+    } else {
+        return Ok(None);
+    };
+
+    let response = crate::http::CLIENT.get(image_url).send().await?;
+    if let Some(len) = response.content_length() {
+        if len > max_size {
+            return Ok(None);
+        }
+    }
+    let bytes = response.bytes().await?;
+    if bytes.len() as u64 > max_size {
+        return Ok(None);
+    }
+
+    std::fs::create_dir_all(assets_dir).expect("cannot create assets directory");
+
+    let extension = Path::new(url_path(image_url))
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("jpg");
+    let file_name = format!("{}.{}", post_id, extension);
+    let file_path = assets_dir.join(&file_name);
+    std::fs::write(&file_path, &bytes).expect("cannot write image to assets directory");
+
+    let assets_dir_name = assets_dir
+        .file_name()
+        .expect("assets_dir must have a file name");
+    Ok(Some(Path::new(assets_dir_name).join(file_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_path_strips_query_and_fragment() {
+        assert_eq!(
+            url_path("https://i.redd.it/abc.jpg?width=108&auto=webp&s=xyz"),
+            "https://i.redd.it/abc.jpg"
+        );
+        assert_eq!(url_path("https://example.com/x.png#frag"), "https://example.com/x.png");
+        assert_eq!(url_path("https://example.com/x.png"), "https://example.com/x.png");
+    }
+
+    #[test]
+    fn is_image_url_matches_known_extensions_regardless_of_query_string() {
+        assert!(is_image_url("https://i.redd.it/abc.jpg?width=108"));
+        assert!(is_image_url("https://i.redd.it/abc.JPEG"));
+        assert!(is_image_url("https://i.redd.it/abc.webp#frag"));
+    }
+
+    #[test]
+    fn is_image_url_rejects_non_image_links() {
+        assert!(!is_image_url("https://example.com/article"));
+        assert!(!is_image_url("https://example.com/video.mp4"));
+    }
+}
@@ -13,18 +13,27 @@ use std::path::Path;
 use std::process::Command;
 use tokio;
 
+mod article;
+mod cache;
+mod config;
+mod epub;
+mod http;
+mod images;
+use config::Config;
+
 #[derive(Clap)]
 #[clap(version = "0.0.1", author = "Pablo Oliveira <pablo@sifflez.org>")]
 /// Read latest posts and comments on your favorite subreddit on an eink device.
 ///
 /// Download latests posts from a subreddit with the full comment tree
 /// to a markdown document.
-/// If ebook-convert is available, an ebook can be optionally produced.
+/// Output files ending in .epub are written directly, with no external
+/// dependency. For other ebook formats, ebook-convert is used if available.
 struct Opts {
-    /// Subreddit to retrieve posts from (without /r/)
-    subreddit: String,
-    /// Output file
-    output: String,
+    /// Subreddit to retrieve posts from (without /r/). Not needed when --config is set.
+    subreddit: Option<String>,
+    /// Output file. Not needed when --config is set.
+    output: Option<String>,
     /// Number of posts to retrieve
     #[clap(short, long, default_value = "10")]
     posts: u32,
@@ -38,6 +47,31 @@ struct Opts {
         default_value = "--chapter \"//h:h1\" --smarten-punctuation --markdown-extensions meta"
     )]
     converter_args: String,
+    /// TOML file describing a batch of subreddit jobs to run in one invocation,
+    /// instead of the single subreddit/output pair given on the command line
+    #[clap(long)]
+    config: Option<String>,
+    /// don't download or embed post images
+    #[clap(long)]
+    no_images: bool,
+    /// skip images larger than this many bytes
+    #[clap(long, default_value = "5000000")]
+    max_image_size: u64,
+    /// how long to keep fetched listings/comment trees cached, in seconds (0 disables caching)
+    #[clap(long, default_value = "0")]
+    cache_ttl: u64,
+    /// for link posts, download and inline the full text of the linked article
+    #[clap(long)]
+    fetch_articles: bool,
+    /// don't descend into reply chains past this depth
+    #[clap(long)]
+    max_depth: Option<u32>,
+    /// drop comments (and their replies) scoring below this
+    #[clap(long)]
+    min_score: Option<i64>,
+    /// keep only the highest-scored top-level threads, up to this many
+    #[clap(long)]
+    max_comments: Option<u32>,
     /// verbose output
     #[clap(short, long)]
     verbose: bool,
@@ -49,40 +83,228 @@ fn quote(str: &str) -> String {
     return s.replace("\n", "\n>");
 }
 
-fn parse_comment(comment: &SubredditCommentsData, depth: u32) -> String {
-    let mut output = String::from("\n\n");
-    if let Some(author) = comment.author.as_ref() {
-        output.push_str("** ");
-        output.push_str(author);
-        output.push_str(" -- **\n");
-        output.push_str(comment.body.as_ref().unwrap());
-        output.push_str("\n");
-        match &comment.replies {
-            Some(SubredditReplies::Reply(replies)) => {
-                for reply in &replies.data.children {
-                    let rep_output = parse_comment(&reply.data, depth + 1);
-                    output.push_str(&rep_output);
+/// Renders a unix timestamp as a coarse, human-readable relative time
+/// ("3 hours ago"), since an eink reader cares about roughly how fresh a
+/// post is, not its exact second.
+fn relative_time(created_utc: f64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    let delta = (now - created_utc).max(0.0) as u64;
+
+    let (amount, unit) = if delta < 60 {
+        return String::from("just now");
+    } else if delta < 3600 {
+        (delta / 60, "minute")
+    } else if delta < 86400 {
+        (delta / 3600, "hour")
+    } else if delta < 86400 * 30 {
+        (delta / 86400, "day")
+    } else if delta < 86400 * 365 {
+        (delta / (86400 * 30), "month")
+    } else {
+        (delta / (86400 * 365), "year")
+    };
+    format!("{} {}{} ago", amount, unit, if amount == 1 { "" } else { "s" })
+}
+
+/// Reddit flair is stored as "richtext": an ordered list of parts that are
+/// each either plain text or a named emoji. Flatten it into plain text,
+/// since an eink markdown document can't render inline emoji images.
+fn parse_flair(richtext: &serde_json::Value) -> String {
+    let mut flair = String::new();
+    if let Some(parts) = richtext.as_array() {
+        for part in parts {
+            match part.get("e").and_then(serde_json::Value::as_str) {
+                Some("text") => {
+                    if let Some(text) = part.get("t").and_then(serde_json::Value::as_str) {
+                        flair.push_str(text);
+                    }
+                }
+                Some("emoji") => {
+                    if let Some(name) = part.get("a").and_then(serde_json::Value::as_str) {
+                        flair.push(':');
+                        flair.push_str(name.trim_matches(':'));
+                        flair.push(':');
+                    }
                 }
+                _ => (),
             }
-            Some(SubredditReplies::Str(_)) | None => (),
-        };
+        }
+    }
+    flair
+}
+
+fn format_post_metadata(post: &SubmissionsData) -> String {
+    let mut parts = vec![
+        format!("{} points", post.score as i64),
+        format!("{}% upvoted", (post.upvote_ratio * 100.0).round() as i64),
+        format!("posted {}", relative_time(post.created_utc)),
+    ];
+    if post.over_18 {
+        parts.push(String::from("NSFW"));
+    }
+    if post.stickied {
+        parts.push(String::from("pinned"));
     }
-    return quote(&output);
+    if let Some(richtext) = post.link_flair_richtext.as_ref() {
+        let flair = parse_flair(richtext);
+        if !flair.is_empty() {
+            parts.push(format!("flair: {}", flair));
+        }
+    }
+    format!("*{}*\n\n", parts.join(" \u{b7} "))
+}
+
+fn format_comment_metadata(comment: &SubredditCommentsData) -> String {
+    format!(
+        "{} points, {}",
+        comment.score as i64,
+        relative_time(comment.created_utc)
+    )
 }
 
-async fn parse_post<'a>(
+/// Renders a marker noting that `count` replies were pruned, or an empty
+/// string when nothing was dropped.
+fn more_replies_marker(count: usize) -> String {
+    if count == 0 {
+        return String::new();
+    }
+    format!(
+        "\n\n*\u{2026} {} more repl{} *\n\n",
+        count,
+        if count == 1 { "y" } else { "ies" }
+    )
+}
+
+/// Whether a comment scoring `score` passes the `min_score` filter.
+fn meets_min_score(score: i64, min_score: Option<i64>) -> bool {
+    min_score.map_or(true, |min| score >= min)
+}
+
+/// Whether replies at `depth` are still allowed to descend into their own
+/// replies, given `max_depth`.
+fn within_max_depth(depth: u32, max_depth: Option<u32>) -> bool {
+    max_depth.map_or(true, |max| depth < max)
+}
+
+/// Truncates `items` (already ordered by priority) to at most `max` entries
+/// and returns how many were dropped. `None` keeps everything.
+fn truncate_and_count_pruned<T>(items: &mut Vec<T>, max: Option<u32>) -> usize {
+    let max = match max {
+        Some(max) => max as usize,
+        None => return 0,
+    };
+    let pruned = items.len().saturating_sub(max);
+    items.truncate(max);
+    pruned
+}
+
+/// Renders a comment and its replies, honoring `max_depth` (stop descending
+/// past this depth) and `min_score` (drop comments, and their whole
+/// subtree, scoring below this), leaving a "… N more replies" marker
+/// wherever content was pruned. Returns `None` when this comment itself was
+/// dropped, so the caller can count it towards its own marker.
+fn parse_comment(
+    comment: &SubredditCommentsData,
+    depth: u32,
+    max_depth: Option<u32>,
+    min_score: Option<i64>,
+) -> Option<String> {
+    let author = comment.author.as_ref()?;
+    if !meets_min_score(comment.score as i64, min_score) {
+        return None;
+    }
+
+    let mut output = String::from("\n\n** ");
+    output.push_str(author);
+    output.push_str(" -- ");
+    output.push_str(&format_comment_metadata(comment));
+    output.push_str(" **\n");
+    output.push_str(comment.body.as_ref().unwrap());
+    output.push_str("\n");
+
+    if let Some(SubredditReplies::Reply(replies)) = &comment.replies {
+        if within_max_depth(depth, max_depth) {
+            let mut pruned = 0;
+            for reply in &replies.data.children {
+                match parse_comment(&reply.data, depth + 1, max_depth, min_score) {
+                    Some(rendered) => output.push_str(&rendered),
+                    None => pruned += 1,
+                }
+            }
+            output.push_str(&more_replies_marker(pruned));
+        } else if !replies.data.children.is_empty() {
+            output.push_str(&more_replies_marker(replies.data.children.len()));
+        }
+    }
+
+    Some(quote(&output))
+}
+
+async fn parse_post<'a, C>(
     subreddit: &Subreddit,
     post: &'a SubmissionsData,
-) -> Result<String, RouxError> {
+    assets_dir: &Path,
+    max_image_size: u64,
+    fetch_images: bool,
+    fetch_articles: bool,
+    max_depth: Option<u32>,
+    min_score: Option<i64>,
+    max_comments: Option<u32>,
+    comments_cache: &cache::FetchCache<C>,
+) -> Result<String, RouxError>
+where
+    C: Clone + Send + Sync + 'static,
+{
     let mut output = String::from("#");
     output.push_str(&post.title);
     output.push_str("\n");
+    output.push_str(&format_post_metadata(post));
+
+    if fetch_images {
+        let image = images::fetch_post_image(
+            &post.id,
+            &post.url,
+            &post.thumbnail,
+            assets_dir,
+            max_image_size,
+        )
+        .await
+        .ok()
+        .flatten();
+        if let Some(image_path) = image {
+            output.push_str(&format!("![]({})\n\n", image_path.display()));
+        }
+    }
+
     output.push_str(&post.selftext);
 
-    let comments = subreddit.article_comments(&post.id, None, None).await?;
-    for comment in &comments.data.children {
-        output.push_str(&parse_comment(&comment.data, 0));
+    if fetch_articles && !post.is_self && post.selftext.is_empty() {
+        if let Ok(article_markdown) = article::fetch_article_markdown(&post.url).await {
+            output.push_str("\n\n");
+            output.push_str(&article_markdown);
+        }
     }
+
+    let comments = comments_cache
+        .get_or_fetch(&post.id, || subreddit.article_comments(&post.id, None, None))
+        .await?;
+
+    let mut top_level: Vec<_> = comments.data.children.iter().collect();
+    if max_comments.is_some() {
+        top_level.sort_by_key(|comment| std::cmp::Reverse(comment.data.score as i64));
+    }
+    let mut pruned_top = truncate_and_count_pruned(&mut top_level, max_comments);
+    for comment in top_level {
+        match parse_comment(&comment.data, 0, max_depth, min_score) {
+            Some(rendered) => output.push_str(&rendered),
+            None => pruned_top += 1,
+        }
+    }
+    output.push_str(&more_replies_marker(pruned_top));
+
     return Ok(output);
 }
 
@@ -97,48 +319,281 @@ fn write_markdown_file(path: &Path, output: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn run_ebook_converter(md_path: &Path, opts: &Opts) -> io::Result<()> {
+fn run_ebook_converter(
+    md_path: &Path,
+    output: &str,
+    ebook_convert: &str,
+    converter_args: &str,
+    verbose: bool,
+) -> io::Result<()> {
     let extra_args =
-        shell_words::split(&opts.converter_args).expect("cannot parse convert arguments");
-    let output = Command::new(&opts.ebook_convert)
+        shell_words::split(converter_args).expect("cannot parse convert arguments");
+    let status = Command::new(ebook_convert)
         .arg(&md_path.to_str().unwrap())
-        .arg(&opts.output)
+        .arg(output)
         .args(extra_args)
         .output()?;
 
-    if opts.verbose {
-        println!("ebook-convert status: {}", output.status);
-        println!("{}", String::from_utf8_lossy(&output.stdout));
-        println!("{}", String::from_utf8_lossy(&output.stderr));
+    if verbose {
+        println!("ebook-convert status: {}", status.status);
+        println!("{}", String::from_utf8_lossy(&status.stdout));
+        println!("{}", String::from_utf8_lossy(&status.stderr));
     }
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), RouxError> {
-    let opts: Opts = Opts::parse();
-    let mut output = String::from(format!(
+/// Fetches `posts` latest submissions (with comments) from `subreddit_name` and
+/// writes them to `output`. Markdown output is written as is, `.epub` output
+/// is built natively, and any other extension goes through the ebook
+/// converter. This is the unit of work repeated once per job when a
+/// `--config` batch file is supplied, and run exactly once otherwise.
+async fn run_job<L, C>(
+    subreddit_name: &str,
+    posts: u32,
+    output: &str,
+    ebook_convert: &str,
+    converter_args: &str,
+    fetch_images: bool,
+    max_image_size: u64,
+    fetch_articles: bool,
+    max_depth: Option<u32>,
+    min_score: Option<i64>,
+    max_comments: Option<u32>,
+    listing_cache: &cache::FetchCache<L>,
+    comments_cache: &cache::FetchCache<C>,
+    verbose: bool,
+) -> Result<(), RouxError>
+where
+    L: Clone + Send + Sync + 'static,
+    C: Clone + Send + Sync + 'static,
+{
+    let mut markdown = String::from(format!(
         "---
 title: /r/{}
 ---
 
 ",
-        opts.subreddit
+        subreddit_name
     ));
 
-    let subreddit = Subreddit::new(&opts.subreddit);
-    let latest = subreddit.latest(opts.posts, None).await?;
+    let path = Path::new(output);
+    let md_path = path.with_extension("md");
+    let assets_dir = path.with_file_name(format!(
+        "{}_assets",
+        path.file_stem().unwrap().to_string_lossy()
+    ));
+
+    let subreddit = Subreddit::new(subreddit_name);
+    let listing_key = format!("{}:{}", subreddit_name, posts);
+    let latest = listing_cache
+        .get_or_fetch(&listing_key, || subreddit.latest(posts, None))
+        .await?;
+    let mut chapters = Vec::new();
     for post in latest.data.children {
-        output.push_str(&parse_post(&subreddit, &post.data).await?);
+        let content = parse_post(
+            &subreddit,
+            &post.data,
+            &assets_dir,
+            max_image_size,
+            fetch_images,
+            fetch_articles,
+            max_depth,
+            min_score,
+            max_comments,
+            comments_cache,
+        )
+        .await?;
+        markdown.push_str(&content);
+        chapters.push(epub::Chapter {
+            title: post.data.title.clone(),
+            markdown: content,
+        });
     }
 
-    let path = Path::new(&opts.output);
-    let md_path = path.with_extension("md");
+    write_markdown_file(&md_path, &markdown).expect("cannot write markdown to output file");
 
-    write_markdown_file(&md_path, &output).expect("cannot write markdown to output file");
+    match path.extension().and_then(OsStr::to_str) {
+        Some("md") => (),
+        Some("epub") => {
+            epub::write_epub(path, subreddit_name, &chapters).expect("cannot write epub file");
+        }
+        _ => {
+            run_ebook_converter(&md_path, output, ebook_convert, converter_args, verbose)
+                .expect("Cannot run ebook-convert command");
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), RouxError> {
+    let opts: Opts = Opts::parse();
+    let listing_cache = cache::FetchCache::new(opts.cache_ttl);
+    let comments_cache = cache::FetchCache::new(opts.cache_ttl);
 
-    if path.extension() != Some(OsStr::new("md")) {
-        run_ebook_converter(&md_path, &opts).expect("Cannot run ebook-convert command");
+    if let Some(config_path) = &opts.config {
+        let config = Config::load(Path::new(config_path)).expect("cannot read config file");
+        for job in &config.jobs {
+            let posts = job.posts.unwrap_or(config.posts);
+            let converter_args = job
+                .converter_args
+                .as_ref()
+                .or_else(|| config.converter_args.as_ref())
+                .unwrap_or(&opts.converter_args);
+            run_job(
+                &job.subreddit,
+                posts,
+                &job.output,
+                &opts.ebook_convert,
+                converter_args,
+                !opts.no_images,
+                opts.max_image_size,
+                opts.fetch_articles,
+                opts.max_depth,
+                opts.min_score,
+                opts.max_comments,
+                &listing_cache,
+                &comments_cache,
+                opts.verbose,
+            )
+            .await?;
+        }
+    } else {
+        let subreddit = opts
+            .subreddit
+            .as_ref()
+            .expect("subreddit is required unless --config is set");
+        let output = opts
+            .output
+            .as_ref()
+            .expect("output is required unless --config is set");
+        run_job(
+            subreddit,
+            opts.posts,
+            output,
+            &opts.ebook_convert,
+            &opts.converter_args,
+            !opts.no_images,
+            opts.max_image_size,
+            opts.fetch_articles,
+            opts.max_depth,
+            opts.min_score,
+            opts.max_comments,
+            &listing_cache,
+            &comments_cache,
+            opts.verbose,
+        )
+        .await?;
     }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_flair_joins_text_and_emoji_parts() {
+        let richtext = serde_json::json!([
+            {"e": "text", "t": "Hello "},
+            {"e": "emoji", "a": ":wave:"},
+            {"e": "text", "t": " world"},
+        ]);
+        assert_eq!(parse_flair(&richtext), "Hello :wave: world");
+    }
+
+    #[test]
+    fn parse_flair_ignores_unknown_part_types() {
+        let richtext = serde_json::json!([{"e": "unknown", "t": "nope"}]);
+        assert_eq!(parse_flair(&richtext), "");
+    }
+
+    #[test]
+    fn parse_flair_handles_non_array_input() {
+        assert_eq!(parse_flair(&serde_json::json!(null)), "");
+    }
+
+    #[test]
+    fn relative_time_just_now() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+        assert_eq!(relative_time(now), "just now");
+    }
+
+    #[test]
+    fn relative_time_buckets_by_unit() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+        assert_eq!(relative_time(now - 120.0), "2 minutes ago");
+        assert_eq!(relative_time(now - 3600.0), "1 hour ago");
+        assert_eq!(relative_time(now - 90000.0), "1 day ago");
+    }
+
+    #[test]
+    fn more_replies_marker_is_empty_when_nothing_pruned() {
+        assert_eq!(more_replies_marker(0), "");
+    }
+
+    #[test]
+    fn more_replies_marker_uses_singular_for_one() {
+        assert_eq!(more_replies_marker(1), "\n\n*\u{2026} 1 more reply *\n\n");
+    }
+
+    #[test]
+    fn more_replies_marker_uses_plural_for_many() {
+        assert_eq!(more_replies_marker(3), "\n\n*\u{2026} 3 more replies *\n\n");
+    }
+
+    #[test]
+    fn meets_min_score_keeps_everything_when_unset() {
+        assert!(meets_min_score(-100, None));
+    }
+
+    #[test]
+    fn meets_min_score_drops_strictly_below_threshold() {
+        assert!(!meets_min_score(4, Some(5)));
+        assert!(meets_min_score(5, Some(5)));
+        assert!(meets_min_score(6, Some(5)));
+    }
+
+    #[test]
+    fn within_max_depth_allows_everything_when_unset() {
+        assert!(within_max_depth(1000, None));
+    }
+
+    #[test]
+    fn within_max_depth_stops_at_the_configured_depth() {
+        assert!(within_max_depth(0, Some(1)));
+        assert!(!within_max_depth(1, Some(1)));
+        assert!(!within_max_depth(2, Some(1)));
+    }
+
+    #[test]
+    fn truncate_and_count_pruned_keeps_everything_when_unset() {
+        let mut items = vec![1, 2, 3];
+        let pruned = truncate_and_count_pruned(&mut items, None);
+        assert_eq!(pruned, 0);
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn truncate_and_count_pruned_drops_the_tail_and_counts_it() {
+        let mut items = vec![1, 2, 3, 4, 5];
+        let pruned = truncate_and_count_pruned(&mut items, Some(2));
+        assert_eq!(pruned, 3);
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    #[test]
+    fn truncate_and_count_pruned_prunes_nothing_when_under_the_limit() {
+        let mut items = vec![1, 2];
+        let pruned = truncate_and_count_pruned(&mut items, Some(5));
+        assert_eq!(pruned, 0);
+        assert_eq!(items, vec![1, 2]);
+    }
+}